@@ -1,4 +1,5 @@
 use bevy::prelude::*;
+use std::collections::{BTreeSet, HashMap, VecDeque};
 
 const SQUARE_SIZE: f32 = 50.0;
 const OUT_OF_BOUNDS_TIME: f32 = 5.0;
@@ -7,6 +8,9 @@ fn main() {
     App::new()
     .add_plugins(DefaultPlugins)
     .insert_resource(PhysicsConfig::default())
+    .insert_resource(SpatialHash::default())
+    .insert_resource(SimClock::default())
+    .insert_resource(SnapshotBuffer::default())
 
     .add_message::<Contact>()
     .add_systems(Startup, setup)
@@ -18,6 +22,8 @@ fn main() {
             spawn_square_on_space,
             remove_square_on_hover,
             drag_square,
+            handle_timeline_hotkeys,
+            apply_pending_restore.after(handle_timeline_hotkeys),
             draw_velocity_vectors,
             display_momentum_info,
         ),
@@ -28,25 +34,40 @@ fn main() {
         (
             clear_forces,
             apply_gravity,
+            apply_mouse_spring,     // soft pull toward the cursor for dragged squares
             integrate_velocity,
-            detect_circle_contacts, // multi-directional normals (2D)
+            build_spatial_hash,     // broad phase: bucket bodies into a uniform grid
+        detect_contacts,        // box-box (SAT), box-circle, and circle-circle
+        detect_ccd_contacts,    // swept TOI pass for fast-moving bodies
         solve_contacts,         // impulses + positional correction
+        update_sleep_state,     // deactivate bodies at rest, wake anything sped back up
         integrate_position,
         check_out_of_bounds,    // only print here
+        capture_snapshot,       // record this tick into the rewind ring buffer
+        advance_sim_clock,      // only advances the tick once this step actually ran
         )
-        .chain(),
+        .chain()
+        .run_if(sim_is_live),
     )
     .run();
 }
 
-#[derive(Resource)]
+#[derive(Resource, Clone)]
 struct PhysicsConfig {
-    friction_enabled: bool,
-    restitution: f32,    // 0..=1 (↑↓)
-    gravity: Vec2,       // can set to Vec2::new(0.0, -980.0) if desired
-    linear_damping: f32, // per-second damping when friction_enabled
-    slop: f32,           // penetration slop
-    percent: f32,        // positional correction factor
+    friction_enabled: bool, // toggles the Coulomb friction term at contacts (F)
+    restitution: f32,       // 0..=1 (↑↓)
+    gravity: Vec2,          // can set to Vec2::new(0.0, -980.0) if desired
+    friction_static: f32,   // mu_s, used while the contact is within the static cone
+    friction_dynamic: f32,  // mu_d, used once the contact is sliding
+    air_drag: f32,          // per-second velocity damping in free space (not physical, opt-in)
+    slop: f32,              // penetration slop
+    percent: f32,           // positional correction factor
+    cell_size: f32,         // broad-phase spatial hash cell size
+    drag_stiffness: f32,    // mouse-spring k
+    drag_damping: f32,      // mouse-spring c
+    sleep_linear_threshold: f32,  // speed below which a body is considered "at rest"
+    sleep_angular_threshold: f32, // spin below which a body is considered "at rest"
+    time_to_sleep: f32,           // seconds at rest before a body is put to sleep
 }
 
 impl Default for PhysicsConfig {
@@ -55,9 +76,17 @@ impl Default for PhysicsConfig {
             friction_enabled: true,
             restitution: 0.8,
             gravity: Vec2::ZERO,
-            linear_damping: 2.0,
+            friction_static: 0.6,
+            friction_dynamic: 0.4,
+            air_drag: 0.0,
             slop: 0.01,
+            drag_stiffness: 400.0,
+            drag_damping: 20.0,
+            sleep_linear_threshold: 5.0,
+            sleep_angular_threshold: 0.2,
+            time_to_sleep: 0.5,
             percent: 0.8,
+            cell_size: SQUARE_SIZE * 2.0,
         }
     }
 }
@@ -78,28 +107,61 @@ struct Force(Vec2);
 struct Mass {
     mass: f32,
     inv: f32,
+    inv_inertia: f32,
 }
 impl Mass {
-    fn new(mass: f32) -> Self {
+    // `radius` is the body's circle collider radius, used for the disk moment of inertia
+    // I = 0.5 * m * r^2
+    fn new(mass: f32, radius: f32) -> Self {
+        let inertia = 0.5 * mass * radius * radius;
         Self {
             mass,
             inv: if mass > 0.0 { 1.0 / mass } else { 0.0 },
+            inv_inertia: if inertia > 0.0 { 1.0 / inertia } else { 0.0 },
         }
     }
 }
 
+// Spin of a body about the Z axis (rad/s); squares stayed rotation-locked before this existed
+#[derive(Component, Default)]
+struct AngularVelocity(f32);
+
 // Circle collider gives fully 2D collision normals (multi-directional response)
 #[derive(Component, Copy, Clone)]
 struct ColliderCircle {
     radius: f32,
 }
 
+// Oriented box collider, aligned to the entity's Transform rotation about Z
+#[derive(Component, Copy, Clone)]
+struct ColliderBox {
+    half_extents: Vec2,
+}
+
+// A mouse-grabbed body stays fully dynamic; `apply_mouse_spring` pulls it toward the cursor
+// with a damped spring instead of teleporting it, so momentum through collisions stays honest.
 #[derive(Component)]
 struct Dragging {
-    offset: Vec2,
-    last_cursor_world: Vec2,
+    anchor_local: Vec2, // grabbed point, in the body's local (unrotated) frame
+    cursor_world: Vec2, // latest cursor position in world space (the spring target)
 }
 
+// Opts a body into the swept-circle CCD pass; without it fast movers can tunnel through thin gaps
+#[derive(Component)]
+struct Tunneling;
+
+// Fraction of this tick's dt still owed to a body after a CCD system advanced it to its time-of-impact
+#[derive(Component)]
+struct CcdRemaining(f32);
+
+// How long (seconds) a body has continuously been under the sleep speed thresholds
+#[derive(Component, Default)]
+struct SleepTimer(f32);
+
+// Deactivated: skipped by gravity/integration and treated as immovable in solve_contacts until woken
+#[derive(Component)]
+struct Sleeping;
+
 #[derive(Component)]
 struct OutOfBoundsTimer(f32);
 
@@ -112,6 +174,7 @@ struct Contact {
     b: Entity,
     normal: Vec2,     // points from A -> B
     penetration: f32, // overlap depth
+    point: Vec2,      // world-space contact point; each body's moment arm runs from its center to here
 }
 
 fn setup(mut commands: Commands) {
@@ -175,10 +238,16 @@ fn spawn_square_on_space(
                     Square,
                     Velocity::default(),
                     Force::default(),
-                    Mass::new(1.0),
+                    Mass::new(1.0, SQUARE_SIZE * 0.5),
                     ColliderCircle {
                         radius: SQUARE_SIZE * 0.5,
                     },
+                    ColliderBox {
+                        half_extents: Vec2::splat(SQUARE_SIZE * 0.5),
+                    },
+                    Tunneling,
+                    AngularVelocity::default(),
+                    SleepTimer::default(),
     ));
 }
 
@@ -211,16 +280,14 @@ fn remove_square_on_hover(
 fn drag_square(
     mut commands: Commands,
     mouse: Res<ButtonInput<MouseButton>>,
-    time: Res<Time>,
     windows: Query<&Window>,
     camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
                mut squares: Query<
                (
                    Entity,
-                &mut Transform,
+                &Transform,
                 &ColliderCircle,
-                &mut Velocity,
-                Option<&Dragging>,
+                Option<&mut Dragging>,
                ),
                With<Square>,
                >,
@@ -233,39 +300,31 @@ fn drag_square(
 
     // Start drag
     if mouse.just_pressed(MouseButton::Left) {
-        for (e, t, c, mut v, dragging) in squares.iter_mut() {
+        for (e, t, c, dragging) in squares.iter_mut() {
             if dragging.is_some() {
                 continue;
             }
             let p = t.translation.truncate();
             if cursor_world.distance(p) <= c.radius {
-                v.0 = Vec2::ZERO;
+                let anchor_local = t.rotation.inverse() * (cursor_world - p).extend(0.0);
                 commands.entity(e).insert(Dragging {
-                    offset: p - cursor_world,
-                    last_cursor_world: cursor_world,
+                    anchor_local: anchor_local.truncate(),
+                    cursor_world,
                 });
+                // Grabbing a sleeping square should wake it immediately
+                commands.entity(e).remove::<Sleeping>();
+                commands.entity(e).insert(SleepTimer(0.0));
                 break;
             }
         }
         return;
     }
 
-    // Continue drag
+    // Continue drag: just refresh the spring target, the pull itself happens in FixedUpdate
     if mouse.pressed(MouseButton::Left) {
-        let dt = time.delta_secs().max(1e-6);
-
-        for (e, mut t, _c, mut v, dragging) in squares.iter_mut() {
-            if let Some(d) = dragging {
-                let target = cursor_world + d.offset;
-                t.translation = target.extend(0.0);
-
-                // Maintain a kinematic velocity so collision impulses affect other bodies
-                v.0 = (cursor_world - d.last_cursor_world) / dt;
-
-                commands.entity(e).insert(Dragging {
-                    offset: d.offset,
-                    last_cursor_world: cursor_world,
-                });
+        for (_e, _t, _c, dragging) in squares.iter_mut() {
+            if let Some(mut d) = dragging {
+                d.cursor_world = cursor_world;
             }
         }
         return;
@@ -273,7 +332,7 @@ fn drag_square(
 
     // End drag
     if mouse.just_released(MouseButton::Left) {
-        for (e, _t, _c, _v, dragging) in squares.iter() {
+        for (e, _t, _c, dragging) in squares.iter() {
             if dragging.is_some() {
                 commands.entity(e).remove::<Dragging>();
             }
@@ -288,74 +347,470 @@ fn clear_forces(mut q: Query<&mut Force>) {
     }
 }
 
-fn apply_gravity(cfg: Res<PhysicsConfig>, mut q: Query<(&Mass, &mut Force), Without<Dragging>>) {
+fn apply_gravity(cfg: Res<PhysicsConfig>, mut q: Query<(&Mass, &mut Force), Without<Sleeping>>) {
     for (m, mut f) in &mut q {
         f.0 += cfg.gravity * m.mass;
     }
 }
 
+// Dragged bodies are grabbed by a spring (see `apply_mouse_spring`), not teleported, so they stay
+// fully dynamic here too: gravity, friction, and collisions all keep acting on them.
+fn apply_mouse_spring(cfg: Res<PhysicsConfig>, mut q: Query<(&Transform, &Velocity, &mut Force, &Dragging)>) {
+    for (t, v, mut f, d) in &mut q {
+        let anchor_world = t.translation.truncate() + (t.rotation * d.anchor_local.extend(0.0)).truncate();
+        f.0 += cfg.drag_stiffness * (d.cursor_world - anchor_world) - cfg.drag_damping * v.0;
+    }
+}
+
 fn integrate_velocity(
     cfg: Res<PhysicsConfig>,
     time: Res<Time>, // fixed dt in FixedUpdate [web:71]
-    mut q: Query<(&Mass, &Force, &mut Velocity), Without<Dragging>>,
+    mut q: Query<(&Mass, &Force, &mut Velocity), Without<Sleeping>>,
 ) {
     let dt = time.delta_secs();
 
     for (m, f, mut v) in &mut q {
         v.0 += f.0 * m.inv * dt;
 
-        if cfg.friction_enabled && cfg.linear_damping > 0.0 {
-            let damp = (1.0 - cfg.linear_damping * dt).clamp(0.0, 1.0);
+        // Air drag is a separate, opt-in fudge for free-space damping; real friction now lives
+        // at contacts in solve_contacts so resting stacks settle instead of being sapped of energy.
+        if cfg.air_drag > 0.0 {
+            let damp = (1.0 - cfg.air_drag * dt).clamp(0.0, 1.0);
             v.0 *= damp;
         }
     }
 }
 
-// Multi-directional contact generation: normal is center-to-center unit vector
-fn detect_circle_contacts(
-    q: Query<(Entity, &Transform, &ColliderCircle), With<Square>>,
-                          mut writer: MessageWriter<Contact>,
+// A box's local +X/+Y axes in world space, derived from its Transform's Z rotation
+fn box_axes(t: &Transform) -> (Vec2, Vec2) {
+    let ux = (t.rotation * Vec3::X).truncate();
+    let uy = (t.rotation * Vec3::Y).truncate();
+    (ux, uy)
+}
+
+// Clips the segment `points` to the half-plane normal.dot(p) <= offset, inserting the crossing
+// point when the segment straddles the plane. None if nothing of the segment survives.
+fn clip_segment_to_line(points: [Vec2; 2], normal: Vec2, offset: f32) -> Option<[Vec2; 2]> {
+    let dist0 = normal.dot(points[0]) - offset;
+    let dist1 = normal.dot(points[1]) - offset;
+
+    let mut out = [Vec2::ZERO; 2];
+    let mut count = 0;
+
+    if dist0 <= 0.0 {
+        out[count] = points[0];
+        count += 1;
+    }
+    if dist1 <= 0.0 {
+        out[count] = points[1];
+        count += 1;
+    }
+    if dist0 * dist1 < 0.0 && count < 2 {
+        out[count] = points[0] + (points[1] - points[0]) * (dist0 / (dist0 - dist1));
+        count += 1;
+    }
+
+    if count < 2 { None } else { Some(out) }
+}
+
+// The two vertices of a box's face whose outward normal is `normal`, which must be (close to)
+// +-u or +-v. u/v are the box's local axes in world space.
+fn box_face(center: Vec2, u: Vec2, v: Vec2, half_extents: Vec2, normal: Vec2) -> (Vec2, Vec2) {
+    if normal.dot(u).abs() >= normal.dot(v).abs() {
+        let face_center = center + u * (half_extents.x * normal.dot(u).signum());
+        (face_center + v * half_extents.y, face_center - v * half_extents.y)
+    } else {
+        let face_center = center + v * (half_extents.y * normal.dot(v).signum());
+        (face_center + u * half_extents.x, face_center - u * half_extents.x)
+    }
+}
+
+// 2D SAT over the four face normals (two per box), followed by Sutherland-Hodgman clipping of
+// the incident face against the reference face's side planes to get the actual contact point(s).
+// Returns the normal (A -> B), penetration depth along the axis of least overlap, and a contact
+// point (the midpoint of the clipped manifold), or None if a separating axis was found.
+fn box_box_contact(
+    pa: Vec2,
+    ta: &Transform,
+    ca: &ColliderBox,
+    pb: Vec2,
+    tb: &Transform,
+    cb: &ColliderBox,
+) -> Option<(Vec2, f32, Vec2)> {
+    let (ax, ay) = box_axes(ta);
+    let (bx, by) = box_axes(tb);
+    let center_delta = pb - pa;
+
+    let mut best_overlap = f32::INFINITY;
+    let mut best_axis = Vec2::ZERO;
+    let mut best_is_a = true;
+
+    for (i, axis) in [ax, ay, bx, by].into_iter().enumerate() {
+        let proj_a = ca.half_extents.x * ax.dot(axis).abs() + ca.half_extents.y * ay.dot(axis).abs();
+        let proj_b = cb.half_extents.x * bx.dot(axis).abs() + cb.half_extents.y * by.dot(axis).abs();
+
+        let dist = center_delta.dot(axis);
+        let overlap = proj_a + proj_b - dist.abs();
+        if overlap <= 0.0 {
+            return None; // separating axis found
+        }
+
+        if overlap < best_overlap {
+            best_overlap = overlap;
+            best_axis = if dist < 0.0 { -axis } else { axis };
+            best_is_a = i < 2;
+        }
+    }
+
+    let normal = best_axis.normalize_or_zero();
+
+    // The reference box's face is the one whose outward normal is `normal` (A -> B); the
+    // incident box contributes whichever of its own faces points most directly back at it.
+    let (ref_center, ref_u, ref_v, ref_half, ref_normal, inc_center, inc_u, inc_v, inc_half) =
+        if best_is_a {
+            (pa, ax, ay, ca.half_extents, normal, pb, bx, by, cb.half_extents)
+        } else {
+            (pb, bx, by, cb.half_extents, -normal, pa, ax, ay, ca.half_extents)
+        };
+
+    let (ref_v1, ref_v2) = box_face(ref_center, ref_u, ref_v, ref_half, ref_normal);
+    let inc_normal = [inc_u, -inc_u, inc_v, -inc_v]
+        .into_iter()
+        .min_by(|a, b| a.dot(ref_normal).partial_cmp(&b.dot(ref_normal)).unwrap())
+        .unwrap();
+    let (inc_v1, inc_v2) = box_face(inc_center, inc_u, inc_v, inc_half, inc_normal);
+
+    let tangent = (ref_v2 - ref_v1).normalize_or_zero();
+    let point = clip_segment_to_line([inc_v1, inc_v2], -tangent, -tangent.dot(ref_v1))
+        .and_then(|clipped| clip_segment_to_line(clipped, tangent, tangent.dot(ref_v2)))
+        .map(|clipped| {
+            let ref_offset = ref_normal.dot(ref_v1);
+            let kept: Vec<Vec2> = clipped
+                .into_iter()
+                .filter(|p| ref_normal.dot(*p) - ref_offset <= 0.0)
+                .collect();
+            if kept.is_empty() {
+                (ref_v1 + ref_v2) * 0.5
+            } else {
+                kept.iter().copied().sum::<Vec2>() / kept.len() as f32
+            }
+        })
+        .unwrap_or((ref_v1 + ref_v2) * 0.5); // degenerate clip; SAT already guarantees overlap
+
+    Some((normal, best_overlap, point))
+}
+
+// Clamps the circle center into the box's local frame to find the closest point on the box;
+// the normal is center-minus-closest, falling back to the shallowest face when the center is
+// inside. The contact point is taken on the circle's own surface, facing the box.
+fn box_circle_contact(
+    box_pos: Vec2,
+    box_t: &Transform,
+    bx: &ColliderBox,
+    circle_pos: Vec2,
+    circle_radius: f32,
+) -> Option<(Vec2, f32, Vec2)> {
+    let (ux, uy) = box_axes(box_t);
+    let delta = circle_pos - box_pos;
+    let local = Vec2::new(delta.dot(ux), delta.dot(uy));
+    let clamped = local.clamp(-bx.half_extents, bx.half_extents);
+
+    if clamped != local {
+        // Circle center is outside the box: normal points away from the clamped closest point
+        let closest_world = box_pos + ux * clamped.x + uy * clamped.y;
+        let diff = circle_pos - closest_world;
+        let dist = diff.length();
+        if dist >= circle_radius {
+            return None;
+        }
+        let normal = if dist > 1e-6 {
+            diff / dist
+        } else {
+            (circle_pos - box_pos).normalize_or_zero()
+        };
+        let point = circle_pos - normal * circle_radius;
+        return Some((normal, circle_radius - dist, point));
+    }
+
+    // Circle center is inside the box: push out along the axis with the shallowest penetration
+    let overlap_x = bx.half_extents.x - local.x.abs();
+    let overlap_y = bx.half_extents.y - local.y.abs();
+    let (axis, overlap) = if overlap_x < overlap_y {
+        (ux * local.x.signum(), overlap_x)
+    } else {
+        (uy * local.y.signum(), overlap_y)
+    };
+    let normal = axis.normalize_or_zero();
+    let point = circle_pos - normal * circle_radius;
+    Some((normal, overlap + circle_radius, point))
+}
+
+// Uniform grid broad phase: bodies are bucketed by every cell their bounding circle overlaps,
+// so the narrow phase only has to test pairs that share at least one cell instead of all n^2 pairs.
+#[derive(Resource, Default)]
+struct SpatialHash {
+    cells: HashMap<(i32, i32), Vec<Entity>>,
+}
+
+fn bounding_radius(circle: Option<&ColliderCircle>, bx: Option<&ColliderBox>) -> f32 {
+    // A body can carry both colliders at once (every square does), in which case the box's
+    // corner reaches farther than the inscribed circle, so take whichever extent is larger
+    // rather than just preferring the circle.
+    let circle_r = circle.map_or(0.0, |c| c.radius);
+    let box_r = bx.map_or(0.0, |b| b.half_extents.length());
+    circle_r.max(box_r)
+}
+
+fn build_spatial_hash(
+    cfg: Res<PhysicsConfig>,
+    mut grid: ResMut<SpatialHash>,
+    q: Query<(Entity, &Transform, Option<&ColliderCircle>, Option<&ColliderBox>), With<Square>>,
 ) {
-    let mut combos = q.iter_combinations();
+    grid.cells.clear();
+    let cell_size = cfg.cell_size.max(1.0);
+
+    for (e, t, circle, bx) in &q {
+        let p = t.translation.truncate();
+        let r = bounding_radius(circle, bx);
+
+        let min = ((p - r) / cell_size).floor().as_ivec2();
+        let max = ((p + r) / cell_size).floor().as_ivec2();
+
+        for cx in min.x..=max.x {
+            for cy in min.y..=max.y {
+                grid.cells.entry((cx, cy)).or_default().push(e);
+            }
+        }
+    }
+}
+
+// General narrow-phase contact generation: box-box (SAT), box-circle, and circle-circle.
+// Normal always points from A to B; penetration is the overlap depth along that normal.
+fn detect_contacts(
+    grid: Res<SpatialHash>,
+    q: Query<
+        (&Transform, Option<&ColliderBox>, Option<&ColliderCircle>),
+        With<Square>,
+    >,
+    mut writer: MessageWriter<Contact>,
+) {
+    // Candidate pairs come only from bodies sharing a grid cell; dedupe since a pair can share
+    // several cells. Order each pair so (a, b) and (b, a) collapse to the same set entry, and use
+    // a BTreeSet rather than a HashSet so iteration order is stable for deterministic replay.
+    let mut candidates: BTreeSet<(Entity, Entity)> = BTreeSet::new();
+    for bucket in grid.cells.values() {
+        for i in 0..bucket.len() {
+            for j in (i + 1)..bucket.len() {
+                let (a, b) = (bucket[i], bucket[j]);
+                candidates.insert(if a < b { (a, b) } else { (b, a) });
+            }
+        }
+    }
+
+    for (ea, eb) in candidates {
+        let Ok([(ta, box_a, circle_a), (tb, box_b, circle_b)]) = q.get_many([ea, eb]) else {
+            continue;
+        };
 
-    while let Some([(ea, ta, ca), (eb, tb, cb)]) = combos.fetch_next() {
         let pa = ta.translation.truncate();
         let pb = tb.translation.truncate();
-        let delta = pb - pa;
 
-        let r = ca.radius + cb.radius;
-        let dist2 = delta.length_squared();
-
-        if dist2 < r * r {
-            let dist = dist2.sqrt().max(1e-6);
-            let normal = delta / dist; // ANY direction in 2D
-            let penetration = r - dist;
+        let contact = match (box_a, box_b) {
+            (Some(ba), Some(bb)) => box_box_contact(pa, ta, ba, pb, tb, bb),
+            (Some(ba), None) => {
+                circle_b.and_then(|cb| box_circle_contact(pa, ta, ba, pb, cb.radius))
+            }
+            (None, Some(bb)) => box_circle_contact(pb, tb, bb, pa, circle_a.map_or(0.0, |c| c.radius))
+                .map(|(n, p, point)| (-n, p, point)), // box is B here, so flip the normal back to A -> B
+            (None, None) => circle_a.zip(circle_b).and_then(|(ca, cb)| {
+                let delta = pb - pa;
+                let r = ca.radius + cb.radius;
+                let dist2 = delta.length_squared();
+                if dist2 < r * r {
+                    let dist = dist2.sqrt().max(1e-6);
+                    let normal = delta / dist;
+                    let point = (pa + normal * ca.radius + pb - normal * cb.radius) * 0.5;
+                    Some((normal, r - dist, point))
+                } else {
+                    None
+                }
+            }),
+        };
 
+        if let Some((normal, penetration, point)) = contact {
             writer.write(Contact {
                 a: ea,
                 b: eb,
                 normal,
                 penetration,
+                point,
             });
         }
     }
 }
 
-// Impulse solve + positional correction, with dragged squares treated as kinematic (inv_mass=0)
+// Swept-circle CCD: catches pairs that `detect_contacts` would miss because a fast body
+// fully crosses the other between fixed steps. Only pairs where at least one body carries
+// `Tunneling` and actually moved more than its radius this step pay for the TOI solve.
+fn detect_ccd_contacts(
+    cfg: Res<PhysicsConfig>,
+    time: Res<Time>,
+    mut commands: Commands,
+    mut writer: MessageWriter<Contact>,
+    mut q: Query<
+        (
+            Entity,
+            &mut Transform,
+            &Velocity,
+            Option<&ColliderCircle>,
+            Option<&ColliderBox>,
+            Option<&Tunneling>,
+        ),
+        With<Square>,
+    >,
+) {
+    let dt = time.delta_secs();
+    let cell_size = cfg.cell_size.max(1.0);
+
+    // Bucket like build_spatial_hash, but each body's radius is padded by how far it's about
+    // to move this step, so a fast mover still shares a cell with whatever it's about to sweep
+    // through rather than only what it already overlaps at rest.
+    let mut swept: HashMap<(i32, i32), Vec<Entity>> = HashMap::new();
+    for (e, t, v, circle, bx, _) in &q {
+        let p = t.translation.truncate();
+        let r = bounding_radius(circle, bx) + (v.0 * dt).length();
+
+        let min = ((p - r) / cell_size).floor().as_ivec2();
+        let max = ((p + r) / cell_size).floor().as_ivec2();
+        for cx in min.x..=max.x {
+            for cy in min.y..=max.y {
+                swept.entry((cx, cy)).or_default().push(e);
+            }
+        }
+    }
+
+    let mut candidates: BTreeSet<(Entity, Entity)> = BTreeSet::new();
+    for bucket in swept.values() {
+        for i in 0..bucket.len() {
+            for j in (i + 1)..bucket.len() {
+                let (a, b) = (bucket[i], bucket[j]);
+                candidates.insert(if a < b { (a, b) } else { (b, a) });
+            }
+        }
+    }
+
+    for (ea, eb) in candidates {
+        let Ok([
+            (_, mut ta, va, circle_a, box_a, tunneling_a),
+            (_, mut tb, vb, circle_b, box_b, tunneling_b),
+        ]) = q.get_many_mut([ea, eb])
+        else {
+            continue;
+        };
+
+        if tunneling_a.is_none() && tunneling_b.is_none() {
+            continue;
+        }
+
+        // Use each body's true bounding radius, not just its inscribed circle: a rotated box's
+        // corner can reach well past ColliderCircle.radius, and using the circle alone here let
+        // a spinning box tunnel through gaps this sweep should have caught.
+        let ra = bounding_radius(circle_a, box_a);
+        let rb = bounding_radius(circle_b, box_b);
+
+        let disp_a = va.0 * dt;
+        let disp_b = vb.0 * dt;
+        if disp_a.length() <= ra && disp_b.length() <= rb {
+            continue;
+        }
+
+        let p = ta.translation.truncate() - tb.translation.truncate();
+        let d = disp_a - disp_b;
+        let r = ra + rb;
+
+        let c = p.length_squared() - r * r;
+        if c <= 0.0 {
+            continue; // already overlapping; the discrete pass owns this one
+        }
+
+        let a = d.length_squared();
+        if a <= 1e-9 {
+            continue;
+        }
+        let b = 2.0 * p.dot(d);
+
+        let disc = b * b - 4.0 * a * c;
+        if disc < 0.0 {
+            continue;
+        }
+
+        let t = (-b - disc.sqrt()) / (2.0 * a);
+        if !(0.0..=1.0).contains(&t) {
+            continue;
+        }
+
+        ta.translation += (disp_a * t).extend(0.0);
+        tb.translation += (disp_b * t).extend(0.0);
+
+        let normal = (tb.translation.truncate() - ta.translation.truncate()).normalize_or_zero();
+        let point =
+            (ta.translation.truncate() + normal * ra + tb.translation.truncate() - normal * rb)
+                * 0.5;
+
+        writer.write(Contact {
+            a: ea,
+            b: eb,
+            normal,
+            penetration: 0.0,
+            point,
+        });
+
+        let remaining = 1.0 - t;
+        commands.entity(ea).insert(CcdRemaining(remaining));
+        commands.entity(eb).insert(CcdRemaining(remaining));
+    }
+}
+
+// 2D cross product of two vectors, a scalar (the Z component of the 3D cross product)
+fn cross2d(a: Vec2, b: Vec2) -> f32 {
+    a.x * b.y - a.y * b.x
+}
+
+// 2D cross product of a scalar angular velocity with a radius vector: omega x r
+fn cross_scalar_vec(omega: f32, r: Vec2) -> Vec2 {
+    Vec2::new(-omega * r.y, omega * r.x)
+}
+
+// Impulse solve + positional correction. Dragged squares are fully dynamic (see `apply_mouse_spring`)
+// so they take part here with their real mass, same as everything else.
 fn solve_contacts(
     cfg: Res<PhysicsConfig>,
+    mut commands: Commands,
     mut reader: MessageReader<Contact>,
-    mut q: Query<(&mut Transform, &mut Velocity, &Mass, Option<&Dragging>), With<Square>>,
+    mut q: Query<
+        (
+            &mut Transform,
+            &mut Velocity,
+            &mut AngularVelocity,
+            &Mass,
+            Option<&Sleeping>,
+        ),
+        With<Square>,
+    >,
 ) {
     for c in reader.read() {
-        let Ok([(mut ta, mut va, ma, da), (mut tb, mut vb, mb, db)]) =
+        let Ok([(mut ta, mut va, mut wa, ma, sleeping_a), (mut tb, mut vb, mut wb, mb, sleeping_b)]) =
         q.get_many_mut([c.a, c.b])
         else {
             continue;
         };
 
-        let inv_a = if da.is_some() { 0.0 } else { ma.inv };
-        let inv_b = if db.is_some() { 0.0 } else { mb.inv };
+        // Two mutually sleeping bodies are a no-op; a lone sleeping body acts as immovable
+        // (inv_mass 0) until the approach speed below wakes it for the next tick.
+        let inv_a = if sleeping_a.is_some() { 0.0 } else { ma.inv };
+        let inv_b = if sleeping_b.is_some() { 0.0 } else { mb.inv };
+        let inv_ia = if sleeping_a.is_some() { 0.0 } else { ma.inv_inertia };
+        let inv_ib = if sleeping_b.is_some() { 0.0 } else { mb.inv_inertia };
         let inv_sum = inv_a + inv_b;
         if inv_sum <= 0.0 {
             continue;
@@ -365,43 +820,125 @@ fn solve_contacts(
         let pen = (c.penetration - cfg.slop).max(0.0);
         if pen > 0.0 {
             let correction = c.normal * (pen * cfg.percent / inv_sum);
-            if da.is_none() {
-                ta.translation -= (correction * inv_a).extend(0.0);
-            }
-            if db.is_none() {
-                tb.translation += (correction * inv_b).extend(0.0);
-            }
+            ta.translation -= (correction * inv_a).extend(0.0);
+            tb.translation += (correction * inv_b).extend(0.0);
         }
 
-        // Impulse along normal
-        let rv = vb.0 - va.0;
+        // Contact-point offsets from each center, using the actual point of impact rather than
+        // a collider radius, since that radius is wrong for anything but a circle-circle contact
+        let ra = c.point - ta.translation.truncate();
+        let rb = c.point - tb.translation.truncate();
+
+        let rv = (vb.0 + cross_scalar_vec(wb.0, rb)) - (va.0 + cross_scalar_vec(wa.0, ra));
         let vel_along_normal = rv.dot(c.normal);
 
+        // Wake a sleeping body struck hard enough by the other side of this contact
+        if -vel_along_normal > cfg.sleep_linear_threshold {
+            if sleeping_a.is_some() {
+                commands.entity(c.a).remove::<Sleeping>();
+                commands.entity(c.a).insert(SleepTimer(0.0));
+            }
+            if sleeping_b.is_some() {
+                commands.entity(c.b).remove::<Sleeping>();
+                commands.entity(c.b).insert(SleepTimer(0.0));
+            }
+        }
+
         // If separating, skip
         if vel_along_normal > 0.0 {
             continue;
         }
 
+        let ra_cross_n = cross2d(ra, c.normal);
+        let rb_cross_n = cross2d(rb, c.normal);
+        let ang_denom = ra_cross_n * ra_cross_n * inv_ia + rb_cross_n * rb_cross_n * inv_ib;
+
         let e = cfg.restitution.clamp(0.0, 1.0);
-        let j = -(1.0 + e) * vel_along_normal / inv_sum;
+        let j = -(1.0 + e) * vel_along_normal / (inv_sum + ang_denom);
         let impulse = c.normal * j;
 
-        if da.is_none() {
-            va.0 -= impulse * inv_a;
+        va.0 -= impulse * inv_a;
+        wa.0 -= inv_ia * cross2d(ra, impulse);
+        vb.0 += impulse * inv_b;
+        wb.0 += inv_ib * cross2d(rb, impulse);
+
+        // Coulomb friction along the contact tangent, using the post-normal-impulse relative velocity
+        if cfg.friction_enabled {
+            let rv = (vb.0 + cross_scalar_vec(wb.0, rb)) - (va.0 + cross_scalar_vec(wa.0, ra));
+            let t = (rv - c.normal * rv.dot(c.normal)).normalize_or_zero();
+
+            if t != Vec2::ZERO {
+                let ra_cross_t = cross2d(ra, t);
+                let rb_cross_t = cross2d(rb, t);
+                let tang_denom =
+                    inv_sum + ra_cross_t * ra_cross_t * inv_ia + rb_cross_t * rb_cross_t * inv_ib;
+                let jt = -rv.dot(t) / tang_denom;
+
+                let friction_impulse = if jt.abs() <= cfg.friction_static * j {
+                    t * jt
+                } else {
+                    t * (-cfg.friction_dynamic * j)
+                };
+
+                va.0 -= friction_impulse * inv_a;
+                wa.0 -= inv_ia * cross2d(ra, friction_impulse);
+                vb.0 += friction_impulse * inv_b;
+                wb.0 += inv_ib * cross2d(rb, friction_impulse);
+            }
         }
-        if db.is_none() {
-            vb.0 += impulse * inv_b;
+    }
+}
+
+// Puts bodies that have stayed under the sleep thresholds for long enough to rest, and wakes
+// anything that picks up speed again (e.g. from a fresh, not-yet-processed impulse).
+fn update_sleep_state(
+    cfg: Res<PhysicsConfig>,
+    time: Res<Time>, // fixed dt in FixedUpdate [web:71]
+    mut commands: Commands,
+    mut q: Query<
+        (Entity, &mut Velocity, &mut AngularVelocity, &mut SleepTimer, Option<&Sleeping>),
+        (With<Square>, Without<Dragging>), // a grabbed body never sleeps, so the mouse spring keeps acting on it
+    >,
+) {
+    let dt = time.delta_secs();
+
+    for (e, mut v, mut w, mut timer, sleeping) in &mut q {
+        let at_rest =
+            v.0.length() <= cfg.sleep_linear_threshold && w.0.abs() <= cfg.sleep_angular_threshold;
+
+        if at_rest {
+            timer.0 += dt;
+            if sleeping.is_none() && timer.0 >= cfg.time_to_sleep {
+                v.0 = Vec2::ZERO;
+                w.0 = 0.0;
+                commands.entity(e).insert(Sleeping);
+            }
+        } else {
+            timer.0 = 0.0;
+            if sleeping.is_some() {
+                commands.entity(e).remove::<Sleeping>();
+            }
         }
     }
 }
 
 fn integrate_position(
     time: Res<Time>, // fixed dt in FixedUpdate [web:71]
-    mut q: Query<(&mut Transform, &Velocity), Without<Dragging>>,
+    mut commands: Commands,
+    mut q: Query<
+        (Entity, &mut Transform, &Velocity, &AngularVelocity, Option<&CcdRemaining>),
+        Without<Sleeping>,
+    >,
 ) {
     let dt = time.delta_secs();
-    for (mut t, v) in &mut q {
-        t.translation += (v.0 * dt).extend(0.0);
+    for (e, mut t, v, w, remaining) in &mut q {
+        // A body already advanced to its CCD time-of-impact only owes the leftover fraction of dt
+        let frac = remaining.map_or(1.0, |r| r.0);
+        t.translation += (v.0 * dt * frac).extend(0.0);
+        t.rotate_z(w.0 * dt * frac);
+        if remaining.is_some() {
+            commands.entity(e).remove::<CcdRemaining>();
+        }
     }
 }
 
@@ -437,6 +974,208 @@ fn check_out_of_bounds(
     }
 }
 
+// ---------- TIMELINE (SNAPSHOT/REWIND) ----------
+// Drives whether the FixedUpdate physics chain runs this frame: live unless paused, plus a
+// one-shot exception so a paused sim can still be single-stepped.
+#[derive(Resource, Default)]
+struct SimClock {
+    tick: u64,
+    paused: bool,
+    step_one: bool,
+}
+
+fn sim_is_live(clock: Res<SimClock>) -> bool {
+    !clock.paused || clock.step_one
+}
+
+fn advance_sim_clock(mut clock: ResMut<SimClock>) {
+    clock.tick += 1;
+    clock.step_one = false;
+}
+
+// Per-body state captured into a snapshot; independent of entity IDs so it survives despawn/respawn
+#[derive(Clone, Copy)]
+struct BodySnapshot {
+    transform: Transform,
+    velocity: Vec2,
+    angular_velocity: f32,
+    mass: f32,
+    collider_radius: f32,
+    collider_half_extents: Vec2,
+    sleep_timer: f32,
+    sleeping: bool,
+}
+
+#[derive(Clone)]
+struct Snapshot {
+    tick: u64,
+    bodies: Vec<BodySnapshot>,
+    config: PhysicsConfig,
+}
+
+// Ring buffer of snapshots, one captured per live FixedUpdate tick, that the timeline hotkeys
+// scrub through. `cursor` counts back from the live edge (0 = most recent tick).
+#[derive(Resource)]
+struct SnapshotBuffer {
+    ring: VecDeque<Snapshot>,
+    capacity: usize,
+    cursor: usize,
+    pending_restore: bool,
+}
+
+impl Default for SnapshotBuffer {
+    fn default() -> Self {
+        Self {
+            ring: VecDeque::new(),
+            capacity: 300, // about 4.7s of history at Bevy's default 64Hz fixed step
+            cursor: 0,
+            pending_restore: false,
+        }
+    }
+}
+
+impl SnapshotBuffer {
+    fn scrub(&mut self, delta: i32) {
+        if self.ring.is_empty() {
+            return;
+        }
+        let max = (self.ring.len() - 1) as i32;
+        let new_cursor = (self.cursor as i32 + delta).clamp(0, max) as usize;
+        if new_cursor != self.cursor {
+            self.cursor = new_cursor;
+            self.pending_restore = true;
+        }
+    }
+
+    fn current(&self) -> Option<&Snapshot> {
+        let idx = self.ring.len().checked_sub(1)?.checked_sub(self.cursor)?;
+        self.ring.get(idx)
+    }
+}
+
+// Captures every body in a stable order (sorted by entity index, not ECS iteration order) so a
+// restored snapshot replays bit-identically regardless of archetype internals.
+fn capture_snapshot(
+    clock: Res<SimClock>,
+    cfg: Res<PhysicsConfig>,
+    mut buffer: ResMut<SnapshotBuffer>,
+    q: Query<
+        (
+            Entity,
+            &Transform,
+            &Velocity,
+            &AngularVelocity,
+            &Mass,
+            &ColliderCircle,
+            &ColliderBox,
+            &SleepTimer,
+            Option<&Sleeping>,
+        ),
+        With<Square>,
+    >,
+) {
+    let mut bodies: Vec<(u32, BodySnapshot)> = q
+        .iter()
+        .map(|(e, t, v, w, m, cc, cb, timer, sleeping)| {
+            (
+                e.index(),
+                BodySnapshot {
+                    transform: *t,
+                    velocity: v.0,
+                    angular_velocity: w.0,
+                    mass: m.mass,
+                    collider_radius: cc.radius,
+                    collider_half_extents: cb.half_extents,
+                    sleep_timer: timer.0,
+                    sleeping: sleeping.is_some(),
+                },
+            )
+        })
+        .collect();
+    bodies.sort_by_key(|(idx, _)| *idx);
+
+    buffer.ring.push_back(Snapshot {
+        tick: clock.tick,
+        bodies: bodies.into_iter().map(|(_, b)| b).collect(),
+        config: cfg.clone(),
+    });
+    if buffer.ring.len() > buffer.capacity {
+        buffer.ring.pop_front();
+    }
+    buffer.cursor = 0;
+}
+
+// P pauses/resumes, `.` single-steps one fixed tick, and the left/right arrows scrub the timeline
+fn handle_timeline_hotkeys(
+    keys: Res<ButtonInput<KeyCode>>,
+    mut clock: ResMut<SimClock>,
+    mut buffer: ResMut<SnapshotBuffer>,
+) {
+    if keys.just_pressed(KeyCode::KeyP) {
+        clock.paused = !clock.paused;
+    }
+    if keys.just_pressed(KeyCode::Period) {
+        clock.step_one = true;
+    }
+    if keys.just_pressed(KeyCode::ArrowLeft) {
+        clock.paused = true;
+        buffer.scrub(1); // further into the past
+    }
+    if keys.just_pressed(KeyCode::ArrowRight) {
+        clock.paused = true;
+        buffer.scrub(-1); // back toward the live edge
+    }
+}
+
+// Restores a scrubbed-to snapshot into the world by despawning every square and respawning one
+// per recorded body, matching `spawn_square_on_space`'s components so physics picks back up cleanly.
+fn apply_pending_restore(
+    mut commands: Commands,
+    mut buffer: ResMut<SnapshotBuffer>,
+    mut clock: ResMut<SimClock>,
+    mut cfg: ResMut<PhysicsConfig>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<ColorMaterial>>,
+    existing: Query<Entity, With<Square>>,
+) {
+    if !buffer.pending_restore {
+        return;
+    }
+    buffer.pending_restore = false;
+
+    let Some(snapshot) = buffer.current() else { return };
+    clock.tick = snapshot.tick;
+    *cfg = snapshot.config.clone();
+
+    for e in &existing {
+        commands.entity(e).despawn();
+    }
+
+    for body in &snapshot.bodies {
+        let mut entity = commands.spawn((
+            Mesh2d(meshes.add(Rectangle::new(SQUARE_SIZE, SQUARE_SIZE))),
+            MeshMaterial2d(materials.add(Color::srgb(0.2, 0.7, 0.9))),
+            body.transform,
+            Square,
+            Velocity(body.velocity),
+            Force::default(),
+            AngularVelocity(body.angular_velocity),
+            Mass::new(body.mass, body.collider_radius),
+            ColliderCircle {
+                radius: body.collider_radius,
+            },
+            ColliderBox {
+                half_extents: body.collider_half_extents,
+            },
+            Tunneling,
+            SleepTimer(body.sleep_timer),
+        ));
+        if body.sleeping {
+            entity.insert(Sleeping);
+        }
+    }
+}
+
 // ---------- DEBUG/UI ----------
 fn draw_velocity_vectors(q: Query<(&Transform, &Velocity), With<Square>>, mut gizmos: Gizmos) {
     for (t, v) in &q {
@@ -449,17 +1188,21 @@ fn draw_velocity_vectors(q: Query<(&Transform, &Velocity), With<Square>>, mut gi
 
 fn display_momentum_info(
     cfg: Res<PhysicsConfig>,
-    bodies: Query<(&Velocity, &Mass), With<Square>>,
+    bodies: Query<(&Velocity, &Mass, Option<&Sleeping>), With<Square>>,
                          mut text_q: Query<&mut Text, With<MomentumText>>,
 ) {
     let mut p_total = Vec2::ZERO;
     let mut ke_total = 0.0;
     let mut count = 0usize;
+    let mut asleep = 0usize;
 
-    for (v, m) in &bodies {
+    for (v, m, sleeping) in &bodies {
         p_total += v.0 * m.mass;
         ke_total += 0.5 * m.mass * v.0.length_squared();
         count += 1;
+        if sleeping.is_some() {
+            asleep += 1;
+        }
     }
 
     let Ok(mut text) = text_q.single_mut() else { return };
@@ -473,11 +1216,13 @@ fn display_momentum_info(
     };
 
     **text = format!(
-        "Total Momentum in the scene: ({:.1}, {:.1}) kg x m/s \ntotal Kinetic Energy: {:.1} J\nSquares: {}\nFriction: {}\nRestitution of e: {:.1} ({})\n\nControls:\n press SPACE to spawn | R to remove cube | hold on a cube to Drag/throw\n F to toggle friction | arrow up and down keys = elasticity",
+        "Total Momentum in the scene: ({:.1}, {:.1}) kg x m/s \ntotal Kinetic Energy: {:.1} J\nSquares: {} ({} awake, {} asleep)\nFriction: {}\nRestitution of e: {:.1} ({})\n\nControls:\n press SPACE to spawn | R to remove cube | hold on a cube to Drag/throw\n F to toggle friction | arrow up and down keys = elasticity\n P to pause | . to step one tick | arrow left/right to scrub the timeline",
                      p_total.x,
                      p_total.y,
                      ke_total,
                      count,
+                     count - asleep,
+                     asleep,
                      if cfg.friction_enabled { "ON" } else { "OFF" },
                          cfg.restitution,
                      kind